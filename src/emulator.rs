@@ -4,14 +4,21 @@ extern crate clap;
 mod models;
 mod util;
 mod workers;
-use models::{Message, ReadType};
+use models::{ChipBib, ChipRead, Message, ReadType};
+use util::io::read_bibchip_file;
 use workers::{ClientConnector, ClientPool};
 
-use crate::util::{is_delay, is_file, is_port, signal_handler};
+use crate::util::{
+    is_delay, is_duration, is_file, is_port, is_seconds, is_skew, signal_handler,
+};
 use chrono::{Datelike, Timelike};
 use clap::{App, Arg};
-use futures::{future::select_all, future::Future, future::FutureExt, pin_mut};
+use futures::{
+    future::pending, future::select_all, future::Future, future::FutureExt, pin_mut,
+};
+use std::collections::hash_map::RandomState;
 use std::fs::File;
+use std::hash::BuildHasher;
 use std::io::{BufRead, BufReader, Lines};
 use std::path::Path;
 use std::pin::Pin;
@@ -20,11 +27,16 @@ use tokio::sync::mpsc::{self, Sender};
 use tokio::time::delay_for;
 use std::convert::TryFrom;
 
-fn generate_read(read_type: ReadType) -> String {
+// The tag used when no bib-chip file is given
+const DEFAULT_TAG: &str = "05800319aeeb";
+// A line that isn't a read, sent between reads when emulating status messages
+const STATUS_LINE: &str = "STATUS\r\n";
+
+fn generate_read(read_type: ReadType, tag_id: &str) -> String {
     let now = chrono::Local::now();
     let read = format!(
-        "aa00{}{:>02}{:>02}{:>02}{:>02}{:>02}{:>02}{:>02}",
-        "05800319aeeb0001",
+        "aa00{}0001{:>02}{:>02}{:>02}{:>02}{:>02}{:>02}{:>02}",
+        tag_id,
         now.year() % 100,
         now.month(),
         now.day(),
@@ -33,10 +45,31 @@ fn generate_read(read_type: ReadType) -> String {
         now.second(),
         now.nanosecond() / 10000000
     );
-    let checksum = read[2..34].bytes().map(|b| b as u32).sum::<u32>() as u8;
-    match read_type {
-        ReadType::RAW => format!("{}{:02x}", read, checksum),
-        ReadType::FSLS => format!("{}{:02x}LS", read, checksum)
+    // Leave a blank checksum, and fill it in once the read is complete
+    let read = match read_type {
+        ReadType::RAW => format!("{}00", read),
+        ReadType::FSLS => format!("{}00LS", read),
+    };
+    // Tags are checked to be hex, so the read is always well formed
+    ChipRead::repair(&read).unwrap()
+}
+
+/// Pass a read, or a status line, along to the clients.
+async fn send_to_clients(bus_tx: &mut Sender<Message>, message: Message) {
+    bus_tx
+        .send(message)
+        .await
+        .unwrap_or_else(|_| {
+            println!("\r\x1b[2KError sending read to thread. Maybe no readers are conected?");
+        });
+}
+
+/// Send a line that isn't a read every `interval` seconds, like a reader
+/// sending status messages between reads.
+async fn send_status(interval: u64, mut bus_tx: Sender<Message>) {
+    loop {
+        delay_for(Duration::from_secs(interval)).await;
+        send_to_clients(&mut bus_tx, Message::STATUS(STATUS_LINE.to_owned())).await;
     }
 }
 
@@ -44,7 +77,7 @@ async fn send_reads(
     delay: u64,
     mut file_reader: Option<Lines<BufReader<File>>>,
     mut bus_tx: Sender<Message>,
-    read_type: ReadType
+    read_type: ReadType,
 ) {
     loop {
         // Convert to string
@@ -53,24 +86,60 @@ async fn send_reads(
                 Some(line) => line.unwrap().trim().to_owned(),
                 None => {
                     file_reader = None;
-                    generate_read(read_type)
+                    generate_read(read_type, DEFAULT_TAG)
                 }
             },
-            None => generate_read(read_type),
+            None => generate_read(read_type, DEFAULT_TAG),
         };
         chip_read.push_str("\r\n");
         // Send the read to the threads
-        bus_tx
-            .send(Message::CHIP_READ(chip_read))
-            .await
-            .unwrap_or_else(|_| {
-                println!("\r\x1b[2KError sending read to thread. Maybe no readers are conected?");
-            });
+        send_to_clients(&mut bus_tx, Message::CHIP_READ(chip_read)).await;
         // println!("{} {:?} {:?}", chip_read.len(), chip_read, chip_read.as_bytes());
         delay_for(Duration::from_millis(delay)).await;
     }
 }
 
+/// Get the time in milliseconds after the start of the race that each
+/// finisher crosses the line.
+///
+/// A skew of 1 spreads the finishers evenly over the race. Higher values
+/// bunch them up at the start, with a long tail of stragglers.
+fn race_offsets(count: usize, duration: u64, skew: f64) -> Vec<u64> {
+    (0..count)
+        .map(|i| {
+            let position = (i as f64 + 0.5) / count as f64;
+            (position.powf(skew) * duration as f64 * 1000.0) as u64
+        })
+        .collect()
+}
+
+/// Send one read for each chip over the course of a race, then start a new
+/// race. The finish order is shuffled each race.
+async fn send_race_reads(
+    mut chips: Vec<ChipBib>,
+    duration: u64,
+    skew: f64,
+    mut bus_tx: Sender<Message>,
+    read_type: ReadType,
+) {
+    let offsets = race_offsets(chips.len(), duration, skew);
+    loop {
+        // Sort by a hash that changes every race to shuffle the chips
+        let hash_state = RandomState::new();
+        chips.sort_by_key(|c| hash_state.hash_one(&c.id));
+        let mut elapsed = 0;
+        for (chip, offset) in chips.iter().zip(offsets.iter()) {
+            delay_for(Duration::from_millis(offset - elapsed)).await;
+            elapsed = *offset;
+            let mut chip_read = generate_read(read_type, &chip.id);
+            chip_read.push_str("\r\n");
+            send_to_clients(&mut bus_tx, Message::CHIP_READ(chip_read)).await;
+        }
+        // Wait out the rest of the race before starting the next one
+        delay_for(Duration::from_millis((duration * 1000).saturating_sub(elapsed))).await;
+    }
+}
+
 #[tokio::main]
 async fn main() {
     // Create the flags
@@ -95,15 +164,64 @@ async fn main() {
                 .takes_value(true)
                 .validator(is_file),
         )
+        .arg(
+            Arg::with_name("bibchip")
+                .help("The bib-chip file to get the chips from. Sends one read per chip each race")
+                .short("b")
+                .long("bibchip")
+                .takes_value(true)
+                .validator(is_file)
+                .conflicts_with("file"),
+        )
+        .arg(
+            Arg::with_name("duration")
+                .help("Time from the first to the last finisher in seconds")
+                .short("D")
+                .long("duration")
+                .takes_value(true)
+                .validator(is_duration)
+                .default_value("600"),
+        )
+        .arg(
+            Arg::with_name("skew")
+                .help("How much the finishers bunch up at the start. 1 spreads them evenly")
+                .short("s")
+                .long("skew")
+                .takes_value(true)
+                .validator(is_skew)
+                .default_value("2"),
+        )
         .arg(
             Arg::with_name("delay")
-                .help("Delay between reads")
+                .help("Delay between reads. Ignored with a bib-chip file")
                 .short("d")
                 .long("delay")
                 .takes_value(true)
                 .validator(is_delay)
                 .default_value("1000"),
         )
+        .arg(
+            Arg::with_name("split")
+                .help("Split each read across two writes")
+                .short("S")
+                .long("split")
+                .takes_value(false),
+        )
+        .arg(
+            Arg::with_name("replay")
+                .help("Send all previous reads to clients when they connect")
+                .short("r")
+                .long("replay")
+                .takes_value(false),
+        )
+        .arg(
+            Arg::with_name("status")
+                .help("Send a line that isn't a read every this many seconds. 0 disables")
+                .long("status")
+                .takes_value(true)
+                .validator(is_seconds)
+                .default_value("0"),
+        )
         .arg(
             Arg::with_name("read_type")
                 .help("The type of read the reader is sending")
@@ -132,20 +250,105 @@ async fn main() {
     let delay = matches.value_of("delay").unwrap().parse::<u64>().unwrap();
     let bind_port = matches.value_of("port").unwrap().parse::<u16>().unwrap();
     let read_type = ReadType::try_from(matches.value_of("read_type").unwrap()).unwrap();
+    let duration = matches.value_of("duration").unwrap().parse::<u64>().unwrap();
+    let skew = matches.value_of("skew").unwrap().parse::<f64>().unwrap();
+    let split = matches.is_present("split");
+    let replay = matches.is_present("replay");
+    let status_interval = matches.value_of("status").unwrap().parse::<u64>().unwrap();
+
+    // Get the chips to race with
+    let mut chips: Option<Vec<ChipBib>> = None;
+    if matches.is_present("bibchip") {
+        chips = match read_bibchip_file(matches.value_of("bibchip").unwrap()) {
+            Ok(bib_chips) => Some(
+                bib_chips
+                    .into_iter()
+                    .filter(|c| {
+                        let valid =
+                            c.id.len() == 12 && c.id.chars().all(|ch| ch.is_ascii_hexdigit());
+                        if !valid {
+                            println!("Skipping invalid chip: {}", c.id);
+                        }
+                        valid
+                    })
+                    .collect(),
+            ),
+            Err(error) => {
+                println!("{}", error);
+                None
+            }
+        };
+        let has_chips = match &chips {
+            Some(c) => !c.is_empty(),
+            None => false,
+        };
+        if !has_chips {
+            println!("No valid chips in the bib-chip file. Sending a single chip instead.");
+            chips = None;
+        }
+    }
 
     let (bus_tx, rx) = mpsc::channel::<Message>(1000);
-    let client_pool = ClientPool::new(rx, None, None, false);
-    let connector = ClientConnector::new(bind_port, bus_tx.clone()).await;
+    let client_pool = ClientPool::new(rx, None, None, false, replay, split);
+    let connector = ClientConnector::new(bind_port, bus_tx.clone(), false).await;
 
     let fut_clients = client_pool.begin().fuse();
     let fut_conn = connector.begin().fuse();
     let fut_sig = signal_handler().fuse();
-    let fut_sender = send_reads(delay, file_reader, bus_tx.clone(), read_type).fuse();
+    let sender_bus = bus_tx.clone();
+    let fut_sender = async move {
+        match chips {
+            Some(chips) => {
+                send_race_reads(chips, duration, skew, sender_bus, read_type).await
+            }
+            None => send_reads(delay, file_reader, sender_bus, read_type).await,
+        }
+    }
+    .fuse();
+    let status_bus = bus_tx.clone();
+    let fut_status = async move {
+        match status_interval {
+            // No status lines, so there is nothing to send
+            0 => pending::<()>().await,
+            interval => send_status(interval, status_bus).await,
+        }
+    }
+    .fuse();
 
-    pin_mut!(fut_sender, fut_clients, fut_conn, fut_sig);
+    pin_mut!(fut_sender, fut_status, fut_clients, fut_conn, fut_sig);
     let futures: Vec<Pin<&mut dyn Future<Output = ()>>> =
-        vec![fut_sender, fut_clients, fut_conn, fut_sig];
+        vec![fut_sender, fut_status, fut_clients, fut_conn, fut_sig];
     select_all(futures).await;
     // If any of them finish, end the program as something went wrong
     bus_tx.clone().send(Message::SHUTDOWN).await.unwrap();
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_race_offsets() {
+        assert_eq!(race_offsets(0, 600, 2.0).len(), 0);
+        assert_eq!(race_offsets(4, 100, 1.0), vec![12500, 37500, 62500, 87500]);
+
+        // Finishers should arrive in order, and bunch up at the start
+        let offsets = race_offsets(100, 600, 2.0);
+        assert_eq!(offsets.len(), 100);
+        assert!(offsets.windows(2).all(|w| w[0] <= w[1]));
+        // Half the finishers should be in the first quarter of the race
+        assert!(offsets[49] < 600 * 1000 / 4);
+        assert!(offsets[99] <= 600 * 1000);
+    }
+
+    #[test]
+    fn test_generate_read() {
+        let read = generate_read(ReadType::RAW, "058003799177");
+        let chip_read = ChipRead::try_from(read.as_str());
+        assert!(chip_read.is_ok());
+        assert_eq!(chip_read.unwrap().tag_id, "058003799177");
+
+        let read2 = generate_read(ReadType::FSLS, DEFAULT_TAG);
+        assert!(ChipRead::try_from(read2.as_str()).is_ok());
+    }
+}
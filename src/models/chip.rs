@@ -38,6 +38,42 @@ impl TryFrom<&str> for ReadType {
     }
 }
 
+/// The reason a chip read couldn't be parsed
+#[derive(Debug, Eq, Ord, PartialOrd, PartialEq, Hash, Copy, Clone)]
+pub enum ReadError {
+    // The read isn't 36 or 38 characters long
+    Length,
+    // The read doesn't start with "aa"
+    Prefix,
+    // A FSLS read doesn't end with "FS" or "LS"
+    Suffix,
+    // The body of the read isn't hexadecimal
+    Hex,
+    // The checksum doesn't match the body of the read
+    Checksum,
+    // The date or time in the read is invalid
+    Timestamp,
+}
+
+impl ReadError {
+    pub fn as_str(&self) -> &'static str {
+        match *self {
+            ReadError::Length => "Invalid read length",
+            ReadError::Prefix => "Invalid read prefix",
+            ReadError::Suffix => "Invalid read suffix",
+            ReadError::Hex => "Invalid hex in read",
+            ReadError::Checksum => "Checksum doesn't match",
+            ReadError::Timestamp => "Invalid read timestamp",
+        }
+    }
+}
+
+impl fmt::Display for ReadError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.as_str())
+    }
+}
+
 #[derive(Debug, Eq, Ord, PartialOrd, PartialEq, Clone)]
 pub struct ChipRead {
     pub tag_id: String,
@@ -56,54 +92,81 @@ impl ChipRead {
     }
 }
 
-impl TryFrom<&str> for ChipRead {
-    type Error = &'static str;
+impl ChipRead {
+    /// Calculate the checksum of a read. This is the sum of the characters
+    /// between the prefix and the checksum.
+    ///
+    /// The read must already be framed.
+    fn checksum(chip_read: &str) -> u8 {
+        chip_read[2..34].bytes().map(|b| b as u32).sum::<u32>() as u8
+    }
 
-    fn try_from(read_str: &str) -> Result<Self, Self::Error> {
-        let chip_read = read_str.trim().split_whitespace().next().unwrap();
-        if !(chip_read.len() == 36 || chip_read.len() == 38) {
-            return Err("Invalid read length");
-        }
-        let checksum = chip_read[2..34].bytes().map(|b| b as u32).sum::<u32>() as u8;
-        if format!("{:02x}", checksum) != chip_read[34..36] {
-            return Err("Checksum doesn't match");
-        }
-        let mut read_type = ReadType::RAW;
-        if chip_read.len() == 38 && (&chip_read[37..] != "FS" || &chip_read[37..] != "LS") {
-            read_type = ReadType::FSLS;
-        } else if chip_read.len() == 38 {
-            return Err("Invalid read suffix");
+    /// Check that the checksum of a read matches its body.
+    pub fn validate_checksum(read: &str) -> Result<(), ReadError> {
+        let chip_read = ChipRead::frame(read)?;
+        if format!("{:02x}", ChipRead::checksum(chip_read)) != chip_read[34..36] {
+            return Err(ReadError::Checksum);
         }
+        Ok(())
+    }
+
+    /// Replace the checksum of a read with one calculated from its body.
+    pub fn repair(read: &str) -> Result<String, ReadError> {
+        let chip_read = ChipRead::frame(read)?;
+        Ok(format!(
+            "{}{:02x}{}",
+            &chip_read[..34],
+            ChipRead::checksum(chip_read),
+            &chip_read[36..]
+        ))
+    }
+
+    /// Parse a read, giving the reason if it is invalid.
+    pub fn parse(read: &str) -> Result<ChipRead, ReadError> {
+        ChipRead::validate_checksum(read)?;
+        ChipRead::parse_lenient(read)
+    }
+
+    /// Parse a read without checking the checksum.
+    pub fn parse_lenient(read: &str) -> Result<ChipRead, ReadError> {
+        let chip_read = ChipRead::frame(read)?;
+        let read_type = match chip_read.len() {
+            38 => match &chip_read[36..] {
+                "FS" | "LS" => ReadType::FSLS,
+                _ => return Err(ReadError::Suffix),
+            },
+            _ => ReadType::RAW,
+        };
         if &chip_read[..2] != "aa" {
-            return Err("Invalid read prefix");
+            return Err(ReadError::Prefix);
         }
         let tag_id = chip_read[4..16].to_owned();
         let read_year = match chip_read[20..22].parse::<u16>() {
-            Err(_) => return Err("Invalid Chip Read"),
+            Err(_) => return Err(ReadError::Timestamp),
             Ok(year) => year,
         };
         let read_month = match chip_read[22..24].parse::<u8>() {
-            Err(_) => return Err("Invalid Chip Read"),
+            Err(_) => return Err(ReadError::Timestamp),
             Ok(month) => month,
         };
         let read_day = match chip_read[24..26].parse::<u8>() {
-            Err(_) => return Err("Invalid Chip Read"),
+            Err(_) => return Err(ReadError::Timestamp),
             Ok(day) => day,
         };
         let read_hour = match chip_read[26..28].parse::<u8>() {
-            Err(_) => return Err("Invalid Chip Read"),
+            Err(_) => return Err(ReadError::Timestamp),
             Ok(hour) => hour,
         };
         let read_min = match chip_read[28..30].parse::<u8>() {
-            Err(_) => return Err("Invalid Chip Read"),
+            Err(_) => return Err(ReadError::Timestamp),
             Ok(min) => min,
         };
         let read_sec = match chip_read[30..32].parse::<u8>() {
-            Err(_) => return Err("Invalid Chip Read"),
+            Err(_) => return Err(ReadError::Timestamp),
             Ok(sec) => sec,
         };
         let read_millis = match i32::from_str_radix(&chip_read[32..34], 16) {
-            Err(_) => return Err("Invalid Chip Read"),
+            Err(_) => return Err(ReadError::Timestamp),
             Ok(millis) => (millis * 10) as u16,
         };
         let read_time: Timestamp = Timestamp::new(
@@ -115,12 +178,37 @@ impl TryFrom<&str> for ChipRead {
             read_sec,
             read_millis,
         );
+        // Catch fields that are numbers, but not a real date or time
+        if read_time.to_datetime().is_none() {
+            return Err(ReadError::Timestamp);
+        }
         Ok(ChipRead {
             tag_id: tag_id,
             timestamp: read_time,
             read_type: read_type,
         })
     }
+
+    /// Get the read out of a line, and check that it has the right length
+    /// and that everything up to the suffix is hex.
+    fn frame(read: &str) -> Result<&str, ReadError> {
+        let chip_read = read.split_whitespace().next().unwrap_or("");
+        if !(chip_read.len() == 36 || chip_read.len() == 38) {
+            return Err(ReadError::Length);
+        }
+        if !chip_read.is_ascii() || !chip_read[2..36].chars().all(|c| c.is_ascii_hexdigit()) {
+            return Err(ReadError::Hex);
+        }
+        Ok(chip_read)
+    }
+}
+
+impl TryFrom<&str> for ChipRead {
+    type Error = &'static str;
+
+    fn try_from(read_str: &str) -> Result<Self, Self::Error> {
+        ChipRead::parse(read_str).map_err(|e| e.as_str())
+    }
 }
 
 impl fmt::Display for ChipRead {
@@ -180,4 +268,91 @@ mod tests {
         assert!(read.is_err());
         assert_eq!(read.err().unwrap(), "Invalid read prefix");
     }
+
+    #[test]
+    fn fsls_suffix() {
+        let read = ChipRead::try_from("aa400000000123450a2a01123018455927a7LS");
+        assert!(read.is_ok());
+        assert_eq!(read.unwrap().read_type, ReadType::FSLS);
+
+        let read2 = ChipRead::parse("aa400000000123450a2a01123018455927a7XX");
+        assert_eq!(read2.err().unwrap(), ReadError::Suffix);
+    }
+
+    #[test]
+    fn invalid_hex() {
+        let read = ChipRead::parse("aa400000000123450a2a0112301845592zz7");
+        assert_eq!(read.err().unwrap(), ReadError::Hex);
+
+        let read2 = ChipRead::parse("aa400000000123450a2a01123018455927é");
+        assert_eq!(read2.err().unwrap(), ReadError::Hex);
+    }
+
+    #[test]
+    fn invalid_timestamp() {
+        let read = ChipRead::parse("aa400000000123450a2a01a23018455927a7");
+        assert!(read.is_err());
+        let repaired = ChipRead::repair("aa400000000123450a2a01a23018455927a7").unwrap();
+        assert_eq!(
+            ChipRead::parse(&repaired).err().unwrap(),
+            ReadError::Timestamp
+        );
+        assert_eq!(
+            ChipRead::try_from(repaired.as_str()).err().unwrap(),
+            "Invalid read timestamp"
+        );
+
+        // Month 13
+        let repaired2 = ChipRead::repair("aa400000000123450a2a01133018455927a7").unwrap();
+        assert_eq!(
+            ChipRead::parse(&repaired2).err().unwrap(),
+            ReadError::Timestamp
+        );
+        assert_eq!(
+            ChipRead::parse_lenient("aa400000000123450a2a01133018455927a7")
+                .err()
+                .unwrap(),
+            ReadError::Timestamp
+        );
+    }
+
+    #[test]
+    fn validate_checksum() {
+        assert!(ChipRead::validate_checksum("aa400000000123450a2a01123018455927a7").is_ok());
+        assert!(ChipRead::validate_checksum("aa400000000123450a2a01123018455927a7\r\n").is_ok());
+        assert_eq!(
+            ChipRead::validate_checksum("aa400000000123450a2a01123018455927a8"),
+            Err(ReadError::Checksum)
+        );
+        assert_eq!(
+            ChipRead::validate_checksum("aa400000000123450a2a01123018455927a"),
+            Err(ReadError::Length)
+        );
+    }
+
+    #[test]
+    fn repair() {
+        assert_eq!(
+            ChipRead::repair("aa400000000123450a2a01123018455927ff").unwrap(),
+            "aa400000000123450a2a01123018455927a7"
+        );
+        assert_eq!(
+            ChipRead::repair("aa400000000123450a2a01123018455927ffLS").unwrap(),
+            "aa400000000123450a2a01123018455927a7LS"
+        );
+        assert_eq!(
+            ChipRead::repair("aa400000000123450a2a01123018455927a").err().unwrap(),
+            ReadError::Length
+        );
+    }
+
+    #[test]
+    fn lenient() {
+        let read = ChipRead::parse_lenient("aa400000000123450a2a01123018455927ff");
+        assert!(read.is_ok());
+        assert_eq!(read.unwrap().tag_id, "000000012345");
+
+        let read2 = ChipRead::parse_lenient("ab400000000123450a2a01123018455927ff");
+        assert_eq!(read2.err().unwrap(), ReadError::Prefix);
+    }
 }
@@ -8,6 +8,10 @@ pub enum Message {
     SHUTDOWN,
     // Pass a chip read along to the clients
     CHIP_READ(String),
+    // Pass a line that isn't a read along to the clients
+    STATUS(String),
     // A new client that just connected
     CLIENT(Client),
+    // A new client that wants reads with participant info
+    ENRICHED_CLIENT(Client),
 }
@@ -8,6 +8,7 @@ mod timestamp;
 pub type ReadType = chip::ReadType;
 pub type ChipBib = chip::ChipBib;
 pub type ChipRead = chip::ChipRead;
+pub type ReadError = chip::ReadError;
 pub type Participant = participant::Participant;
 pub type Gender = participant::Gender;
 pub type Timestamp = timestamp::Timestamp;
@@ -1,3 +1,4 @@
+use chrono::{NaiveDate, NaiveDateTime};
 use std::fmt;
 
 #[derive(Debug, Eq, Ord, PartialOrd, PartialEq, Copy, Clone)]
@@ -32,6 +33,17 @@ impl Timestamp {
         }
     }
 
+    /// Convert to a date and time. Returns None if the date is invalid.
+    pub fn to_datetime(self) -> Option<NaiveDateTime> {
+        NaiveDate::from_ymd_opt(2000 + self.year as i32, self.month as u32, self.day as u32)?
+            .and_hms_milli_opt(
+                self.hour as u32,
+                self.minute as u32,
+                self.second as u32,
+                self.millis as u32,
+            )
+    }
+
     pub fn time_string(&self) -> String {
         format!(
             "{:02}:{:02}:{:02}.{:03}",
@@ -50,3 +62,23 @@ impl fmt::Display for Timestamp {
         )
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn to_datetime() {
+        let time = Timestamp::new(1, 12, 30, 18, 45, 59, 390).to_datetime();
+        assert_eq!(
+            time,
+            NaiveDate::from_ymd_opt(2001, 12, 30)
+                .unwrap()
+                .and_hms_milli_opt(18, 45, 59, 390)
+        );
+
+        assert!(Timestamp::new(1, 13, 30, 18, 45, 59, 390).to_datetime().is_none());
+        assert!(Timestamp::new(1, 2, 30, 18, 45, 59, 390).to_datetime().is_none());
+        assert!(Timestamp::new(1, 12, 30, 24, 45, 59, 390).to_datetime().is_none());
+    }
+}
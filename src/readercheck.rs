@@ -0,0 +1,319 @@
+#[macro_use]
+extern crate clap;
+
+// Only the read models, so the workers that messages carry aren't needed
+mod models {
+    #![allow(dead_code)]
+    mod chip;
+    mod participant;
+    mod timestamp;
+
+    pub type ChipBib = chip::ChipBib;
+    pub type ChipRead = chip::ChipRead;
+    pub type ReadError = chip::ReadError;
+    pub type Participant = participant::Participant;
+    pub type Timestamp = timestamp::Timestamp;
+}
+mod util;
+use models::{ChipRead, ReadError};
+use util::{is_duration, is_socket_addr};
+
+use chrono::{Local, NaiveDateTime};
+use clap::{App, Arg};
+use std::collections::BTreeMap;
+use std::fmt;
+use std::io::{self, Write};
+use std::net::SocketAddrV4;
+use std::process;
+use std::time::{Duration, Instant};
+use tokio::io::{AsyncBufRead, AsyncBufReadExt, BufReader};
+use tokio::net::TcpStream;
+use tokio::time::timeout;
+
+// How long to wait for the reader to accept the connection
+const CONNECT_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// The results of checking the reads from a reader
+#[derive(Debug, Default)]
+struct Report {
+    valid: u32,
+    invalid: BTreeMap<ReadError, u32>,
+    // Lines that aren't reads, like status messages
+    other_lines: u32,
+    // Time from the reader's timestamp to the read arriving, in milliseconds
+    latencies: Vec<i64>,
+    disconnected: bool,
+}
+
+impl Report {
+    /// Check a line from the reader, and add it to the report.
+    ///
+    /// Lines that look like reads (start with "aa" or are the length of a
+    /// read) are checked. Anything else counts as some other message.
+    fn add_line(&mut self, line: &str, received: NaiveDateTime) {
+        let line = line.trim();
+        if line.is_empty() {
+            return;
+        }
+        if !(line.starts_with("aa") || line.len() == 36 || line.len() == 38) {
+            self.other_lines += 1;
+            return;
+        }
+        match ChipRead::parse(line) {
+            Ok(chip_read) => match chip_read.timestamp.to_datetime() {
+                Some(time) => {
+                    self.valid += 1;
+                    self.latencies.push((received - time).num_milliseconds());
+                }
+                None => self.add_error(ReadError::Timestamp),
+            },
+            Err(error) => self.add_error(error),
+        }
+    }
+
+    fn add_error(&mut self, error: ReadError) {
+        *self.invalid.entry(error).or_insert(0) += 1;
+    }
+
+    fn invalid_count(&self) -> u32 {
+        self.invalid.values().sum()
+    }
+
+    /// The reader passes if it stayed connected and sent only valid reads.
+    fn passed(&self) -> bool {
+        self.valid > 0 && self.invalid.is_empty() && !self.disconnected
+    }
+}
+
+impl fmt::Display for Report {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        writeln!(f, "Valid reads: {}", self.valid)?;
+        writeln!(f, "Invalid reads: {}", self.invalid_count())?;
+        for (error, count) in self.invalid.iter() {
+            writeln!(f, "    {}: {}", error, count)?;
+        }
+        writeln!(f, "Other lines: {}", self.other_lines)?;
+        match (self.latencies.iter().min(), self.latencies.iter().max()) {
+            (Some(min), Some(max)) => writeln!(
+                f,
+                "Latency (includes any clock difference): min {}ms, avg {}ms, max {}ms",
+                min,
+                self.latencies.iter().sum::<i64>() / self.latencies.len() as i64,
+                max
+            )?,
+            _ => writeln!(f, "Latency: no valid reads")?,
+        };
+        if self.disconnected {
+            writeln!(f, "Reader disconnected during the check")?;
+        }
+        writeln!(
+            f,
+            "Result: {}",
+            match self.passed() {
+                true => "PASS",
+                false => "FAIL",
+            }
+        )
+    }
+}
+
+/// Check every line the reader sends until the time runs out.
+async fn check_reads<R: AsyncBufRead + Unpin>(reader: &mut R, duration: Duration) -> Report {
+    let mut report = Report::default();
+    let mut line = Vec::new();
+    let deadline = Instant::now() + duration;
+    loop {
+        let remaining = deadline.saturating_duration_since(Instant::now());
+        line.clear();
+        let result = match timeout(remaining, reader.read_until(b'\n', &mut line)).await {
+            Ok(result) => result,
+            // Out of time
+            Err(_) => break,
+        };
+        match result {
+            Ok(0) => {
+                println!("\r\x1b[2KReader closed the connection");
+                report.disconnected = true;
+                break;
+            }
+            Ok(_) => {}
+            Err(error) => {
+                println!("\r\x1b[2KError reading from reader: {}", error);
+                report.disconnected = true;
+                break;
+            }
+        }
+        report.add_line(&String::from_utf8_lossy(&line), Local::now().naive_local());
+        print!(
+            "\r\x1b[2KValid reads: {} Invalid reads: {} Other lines: {}",
+            report.valid,
+            report.invalid_count(),
+            report.other_lines
+        );
+        io::stdout().flush().unwrap_or(());
+    }
+    println!();
+    report
+}
+
+#[tokio::main]
+async fn main() {
+    // Create the flags
+    let matches = App::new("Rusty Timer: Reader Check")
+        .version(crate_version!())
+        .author("Isaac Wismer")
+        .about("Checks that a reader is sending valid reads")
+        .arg(
+            Arg::with_name("reader")
+                .help("The socket address of the reader to check. Eg. 192.168.0.52:10000")
+                .index(1)
+                .takes_value(true)
+                .value_name("reader_ip")
+                .validator(is_socket_addr)
+                .required(true),
+        )
+        .arg(
+            Arg::with_name("duration")
+                .help("How long to check reads for in seconds")
+                .short("d")
+                .long("duration")
+                .takes_value(true)
+                .validator(is_duration)
+                .default_value("30"),
+        )
+        .get_matches();
+
+    let addr = matches
+        .value_of("reader")
+        .unwrap()
+        .parse::<SocketAddrV4>()
+        .unwrap();
+    let duration = matches.value_of("duration").unwrap().parse::<u64>().unwrap();
+    let duration = Duration::from_secs(duration);
+
+    let stream = match timeout(CONNECT_TIMEOUT, TcpStream::connect(addr)).await {
+        Ok(Ok(stream)) => stream,
+        Ok(Err(error)) => {
+            println!("Failed to connect to reader: {}", error);
+            println!("Result: FAIL");
+            process::exit(1);
+        }
+        Err(_) => {
+            println!("Timed out connecting to reader: {}", addr);
+            println!("Result: FAIL");
+            process::exit(1);
+        }
+    };
+    println!(
+        "Connected to reader: {}. Pass a test tag over it in the next {}s.",
+        addr,
+        duration.as_secs()
+    );
+
+    let report = check_reads(&mut BufReader::new(stream), duration).await;
+    print!("{}", report);
+    if !report.passed() {
+        process::exit(1);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::NaiveDate;
+
+    fn received() -> NaiveDateTime {
+        NaiveDate::from_ymd_opt(2001, 12, 30)
+            .unwrap()
+            .and_hms_milli_opt(18, 46, 0, 0)
+            .unwrap()
+    }
+
+    #[test]
+    fn valid_reads() {
+        let mut report = Report::default();
+        report.add_line("aa400000000123450a2a01123018455927a7\r\n", received());
+        report.add_line("aa400000000123450a2a01123018455927a7\r\n", received());
+        assert_eq!(report.valid, 2);
+        assert_eq!(report.invalid_count(), 0);
+        assert_eq!(report.latencies, vec![610, 610]);
+        assert!(report.passed());
+    }
+
+    #[test]
+    fn invalid_reads() {
+        let mut report = Report::default();
+        report.add_line("aa400000000123450a2a01123018455927a7\r\n", received());
+        report.add_line("aa400000000123450a2a01123018455927a8\r\n", received());
+        report.add_line("ab400000000123450a2a01123018455927a7\r\n", received());
+        report.add_line("aa400000000123450a2a01123018455927a8\r\n", received());
+        assert_eq!(report.valid, 1);
+        assert_eq!(report.invalid_count(), 3);
+        assert_eq!(report.invalid.get(&ReadError::Checksum), Some(&2));
+        assert_eq!(report.invalid.get(&ReadError::Prefix), Some(&1));
+        assert!(!report.passed());
+
+        // Reasons are always listed in the same order
+        let text = report.to_string();
+        assert!(
+            text.find("Invalid read prefix").unwrap()
+                < text.find("Checksum doesn't match").unwrap()
+        );
+    }
+
+    #[test]
+    fn invalid_date() {
+        let mut report = Report::default();
+        // Month 13, with a good checksum
+        let read = ChipRead::repair("aa400000000123450a2a01133018455927a7").unwrap();
+        report.add_line(&read, received());
+        assert_eq!(report.valid, 0);
+        assert_eq!(report.invalid.get(&ReadError::Timestamp), Some(&1));
+        assert!(report.latencies.is_empty());
+        assert!(!report.passed());
+    }
+
+    #[test]
+    fn status_lines() {
+        let mut report = Report::default();
+        report.add_line("aa400000000123450a2a01123018455927a7\r\n", received());
+        report.add_line("STATUS\r\n", received());
+        report.add_line("\r\n", received());
+        report.add_line("aa400000000123450a2a01123018455927a7\r\n", received());
+        assert_eq!(report.valid, 2);
+        assert_eq!(report.invalid_count(), 0);
+        assert_eq!(report.other_lines, 1);
+        assert!(report.passed());
+    }
+
+    #[tokio::test]
+    async fn status_line_between_reads() {
+        // A status line shouldn't throw off the reads after it
+        let data = "aa400000000123450a2a01123018455927a7\r\n\
+                    STATUS\r\n\
+                    aa400000000123450a2a01123018455927a7LS\r\n\
+                    aa400000000123450a2a01123018455927a7\r\n";
+        let mut reader = BufReader::new(data.as_bytes());
+        let report = check_reads(&mut reader, Duration::from_secs(5)).await;
+        assert_eq!(report.valid, 3);
+        assert_eq!(report.invalid_count(), 0);
+        assert_eq!(report.other_lines, 1);
+        // The test data ends, which looks like the reader disconnecting
+        assert!(report.disconnected);
+    }
+
+    #[test]
+    fn no_reads() {
+        let report = Report::default();
+        assert!(!report.passed());
+        assert!(report.to_string().ends_with("Result: FAIL\n"));
+    }
+
+    #[test]
+    fn disconnected() {
+        let mut report = Report::default();
+        report.add_line("aa400000000123450a2a01123018455927a7\r\n", received());
+        report.disconnected = true;
+        assert!(!report.passed());
+    }
+}
@@ -1,12 +1,15 @@
 #[macro_use]
 extern crate clap;
 
-use clap::{App, Arg};
-use futures::{future::select_all, future::Future, future::FutureExt, pin_mut};
+use clap::{App, Arg, Error, ErrorKind};
+use futures::{
+    future::pending, future::select_all, future::Future, future::FutureExt, pin_mut,
+};
 use rusqlite::types::ToSql;
 use rusqlite::{Connection, NO_PARAMS};
 use std::net::SocketAddrV4;
 use std::pin::Pin;
+use std::time::Duration;
 use tokio::sync::mpsc;
 use std::convert::TryInto;
 
@@ -23,9 +26,12 @@ struct Args {
     participants_file_path: Option<String>,
     readers: Vec<SocketAddrV4>,
     bind_port: u16,
+    enriched_port: Option<u16>,
     out_file: Option<String>,
     buffered_output: bool,
     read_type: ReadType,
+    idle_timeout: Option<Duration>,
+    max_drift: Option<Duration>,
 }
 
 fn get_args() -> Args {
@@ -53,6 +59,15 @@ fn get_args() -> Args {
                 .validator(is_port)
                 .default_value("10001"),
         )
+        .arg(
+            Arg::with_name("enriched_port")
+                .help("The port to send reads with participant info to")
+                .short("e")
+                .long("enriched-port")
+                .takes_value(true)
+                .validator(is_port)
+                .requires("bibchip"),
+        )
         .arg(
             Arg::with_name("read_type")
                 .help("The type of read the reader is sending")
@@ -62,6 +77,24 @@ fn get_args() -> Args {
                 .possible_values(&["raw", "fsls"])
                 .default_value("raw"),
         )
+        .arg(
+            Arg::with_name("idle_timeout")
+                .help("Reconnect to a reader after this many seconds without a complete read. The reader may re-send its buffered reads on reconnect. 0 disables")
+                .short("i")
+                .long("idle-timeout")
+                .takes_value(true)
+                .validator(is_seconds)
+                .default_value("0"),
+        )
+        .arg(
+            Arg::with_name("max_drift")
+                .help("Warn if a reader's clock is more than this many seconds off. 0 disables")
+                .short("m")
+                .long("max-drift")
+                .takes_value(true)
+                .validator(is_seconds)
+                .default_value("0"),
+        )
         .arg(
             Arg::with_name("file")
                 .help("The file to output the reads to")
@@ -103,15 +136,39 @@ fn get_args() -> Args {
         .collect();
     // parse the port value
     let bind_port = matches.value_of("port").unwrap().parse::<u16>().unwrap();
+    let enriched_port = matches
+        .value_of("enriched_port")
+        .map(|p| p.parse::<u16>().unwrap());
+    // Port 0 lets the OS pick, so only a fixed port can clash
+    if bind_port != 0 && enriched_port == Some(bind_port) {
+        Error::with_description(
+            "The enriched port must be different from the port",
+            ErrorKind::ArgumentConflict,
+        )
+        .exit();
+    }
+    // A timeout of 0 means never treat a reader as stale
+    let idle_timeout = match matches.value_of("idle_timeout").unwrap().parse::<u64>().unwrap() {
+        0 => None,
+        secs => Some(Duration::from_secs(secs)),
+    };
+    // A max drift of 0 means never check the reader clocks
+    let max_drift = match matches.value_of("max_drift").unwrap().parse::<u64>().unwrap() {
+        0 => None,
+        secs => Some(Duration::from_secs(secs)),
+    };
 
     Args {
         bib_chip_file_path: matches.value_of("bibchip").map(|s| s.to_owned()),
         participants_file_path: matches.value_of("participants").map(|s| s.to_owned()),
         readers: readers,
         bind_port,
+        enriched_port,
         out_file: matches.value_of("file").map(|s| s.to_owned()),
         buffered_output: matches.is_present("is_buffered"),
-        read_type: matches.value_of("read_type").unwrap().try_into().unwrap()
+        read_type: matches.value_of("read_type").unwrap().try_into().unwrap(),
+        idle_timeout,
+        max_drift,
     }
 }
 
@@ -180,18 +237,41 @@ async fn main() {
     // Bus to send messages to client pool
     let (bus_tx, rx) = mpsc::channel::<Message>(1000);
 
-    let client_pool = ClientPool::new(rx, Some(conn), args.out_file, args.buffered_output);
-    let connector = ClientConnector::new(args.bind_port, bus_tx.clone()).await;
-    let mut reader_pool = ReaderPool::new(args.readers, bus_tx.clone(), args.read_type);
+    let client_pool = ClientPool::new(rx, Some(conn), args.out_file, args.buffered_output, false, false);
+    let connector = ClientConnector::new(args.bind_port, bus_tx.clone(), false).await;
+    let enriched_connector = match args.enriched_port {
+        Some(port) => Some(ClientConnector::new(port, bus_tx.clone(), true).await),
+        None => None,
+    };
+    let mut reader_pool = ReaderPool::new(
+        args.readers,
+        bus_tx.clone(),
+        args.read_type,
+        args.idle_timeout,
+        args.max_drift,
+    );
 
     let fut_readers = reader_pool.begin().fuse();
     let fut_clients = client_pool.begin().fuse();
     let fut_conn = connector.begin().fuse();
+    let fut_enriched_conn = async move {
+        match enriched_connector {
+            Some(connector) => connector.begin().await,
+            // No participant port, so there is nothing to listen for
+            None => pending::<()>().await,
+        }
+    }
+    .fuse();
     let fut_sig = signal_handler().fuse();
 
-    pin_mut!(fut_readers, fut_clients, fut_conn, fut_sig);
-    let futures: Vec<Pin<&mut dyn Future<Output = ()>>> =
-        vec![fut_readers, fut_clients, fut_conn, fut_sig];
+    pin_mut!(fut_readers, fut_clients, fut_conn, fut_enriched_conn, fut_sig);
+    let futures: Vec<Pin<&mut dyn Future<Output = ()>>> = vec![
+        fut_readers,
+        fut_clients,
+        fut_conn,
+        fut_enriched_conn,
+        fut_sig,
+    ];
     select_all(futures).await;
     // If any of them finish, end the program as something went wrong
     bus_tx.clone().send(Message::SHUTDOWN).await.unwrap();
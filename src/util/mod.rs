@@ -67,6 +67,31 @@ pub fn is_delay(delay: String) -> Result<(), String> {
     }
 }
 
+/// Check that the string is a valid duration in seconds, which must be at
+/// least 1
+pub fn is_duration(duration: String) -> Result<(), String> {
+    match duration.parse::<u64>() {
+        Ok(d) if d >= 1 => Ok(()),
+        _ => Err("Invalid duration. Must be at least 1 second".to_owned()),
+    }
+}
+
+/// Check that the string is a valid skew, which must be at least 1
+pub fn is_skew(skew: String) -> Result<(), String> {
+    match skew.parse::<f64>() {
+        Ok(s) if s >= 1.0 => Ok(()),
+        _ => Err("Invalid skew value. Must be at least 1".to_owned()),
+    }
+}
+
+/// Check that the string is a valid number of seconds
+pub fn is_seconds(seconds: String) -> Result<(), String> {
+    match seconds.parse::<u64>() {
+        Ok(_) => Ok(()),
+        Err(_) => Err("Invalid number of seconds".to_owned()),
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -148,4 +173,43 @@ mod tests {
         assert!(is_delay("foobar".to_owned()).is_err());
         assert!(is_delay("".to_owned()).is_err());
     }
+
+    #[test]
+    fn test_is_seconds() {
+        assert!(is_seconds("0".to_owned()).is_ok());
+        assert!(is_seconds("30".to_owned()).is_ok());
+        assert!(is_seconds("3600".to_owned()).is_ok());
+        assert!(is_seconds("5000000000".to_owned()).is_ok());
+
+        assert!(is_seconds("-1".to_owned()).is_err());
+        assert!(is_seconds("1.5".to_owned()).is_err());
+        assert!(is_seconds("foobar".to_owned()).is_err());
+        assert!(is_seconds("".to_owned()).is_err());
+    }
+
+    #[test]
+    fn test_is_duration() {
+        assert!(is_duration("1".to_owned()).is_ok());
+        assert!(is_duration("30".to_owned()).is_ok());
+        assert!(is_duration("600".to_owned()).is_ok());
+
+        assert!(is_duration("0".to_owned()).is_err());
+        assert!(is_duration("-1".to_owned()).is_err());
+        assert!(is_duration("1.5".to_owned()).is_err());
+        assert!(is_duration("foobar".to_owned()).is_err());
+        assert!(is_duration("".to_owned()).is_err());
+    }
+
+    #[test]
+    fn test_is_skew() {
+        assert!(is_skew("1".to_owned()).is_ok());
+        assert!(is_skew("2.5".to_owned()).is_ok());
+        assert!(is_skew("10".to_owned()).is_ok());
+
+        assert!(is_skew("0.5".to_owned()).is_err());
+        assert!(is_skew("-1".to_owned()).is_err());
+        assert!(is_skew("NaN".to_owned()).is_err());
+        assert!(is_skew("foobar".to_owned()).is_err());
+        assert!(is_skew("".to_owned()).is_err());
+    }
 }
@@ -1,37 +1,72 @@
 use std::net::Shutdown;
 use std::net::SocketAddr;
+use std::time::Duration;
 use tokio::net::TcpStream;
 use tokio::prelude::*;
+use tokio::sync::mpsc::{self, Receiver, Sender};
+use tokio::time::delay_for;
+
+// The most writes waiting for a client before it is dropped as too slow
+const QUEUE_SIZE: usize = 1000;
+// Time between the two halves of a split read, in milliseconds
+const SPLIT_DELAY: u64 = 50;
+
+/// Write everything queued for a client, until the client is removed or the
+/// connection fails. Each write is made up of parts, with a short pause
+/// between them.
+async fn write_to_client(mut stream: TcpStream, mut queue: Receiver<Vec<String>>) {
+    while let Some(parts) = queue.recv().await {
+        for (i, part) in parts.iter().enumerate() {
+            if i > 0 {
+                delay_for(Duration::from_millis(SPLIT_DELAY)).await;
+            }
+            // Dropping the queue makes the next send fail, so the client
+            // gets removed
+            if stream.write_all(part.as_bytes()).await.is_err() {
+                return;
+            }
+        }
+    }
+    match stream.shutdown(Shutdown::Both) {
+        Ok(_) => println!("\r\x1b[2KClient disconnected gracefully."),
+        Err(e) => eprintln!("\r\x1b[2KError disconnecting: {}", e),
+    };
+}
 
 /// Holds a connection to a single client, and forwards reads to it.
+///
+/// The writes happen in a separate task, so a slow client doesn't hold up
+/// the others.
 #[derive(Debug)]
 pub struct Client {
-    stream: TcpStream,
     addr: SocketAddr,
+    queue: Sender<Vec<String>>,
 }
 
 impl Client {
     pub fn new(stream: TcpStream, addr: SocketAddr) -> Result<Client, &'static str> {
-        Ok(Client {
-            stream: stream,
-            addr,
-        })
+        let (queue, rx) = mpsc::channel(QUEUE_SIZE);
+        tokio::spawn(write_to_client(stream, rx));
+        Ok(Client { addr, queue })
     }
 
     /// Send a single read to the connected client.
-    pub async fn send_read(&mut self, read: String) -> Result<usize, SocketAddr> {
-        self.stream
-            .write(read.as_bytes())
-            .await
-            .map_err(|_| self.addr)
+    ///
+    /// Fails if the client has disconnected, or has fallen too far behind.
+    pub fn send_read(&mut self, read: String) -> Result<(), SocketAddr> {
+        self.queue.try_send(vec![read]).map_err(|_| self.addr)
+    }
+
+    /// Send a read to the connected client in two writes, like a reader
+    /// whose reads get broken across TCP packets.
+    pub fn send_split_read(&mut self, mut read: String) -> Result<(), SocketAddr> {
+        let end = read.split_off(read.len() / 2);
+        self.queue.try_send(vec![read, end]).map_err(|_| self.addr)
     }
 
-    /// Close the connection to the client.
-    pub fn exit(&self) {
-        match self.stream.shutdown(Shutdown::Both) {
-            Ok(_) => println!("\r\x1b[2KClient disconnected gracefully."),
-            Err(e) => eprintln!("\r\x1b[2KError disconnecting: {}", e),
-        };
+    /// Close the connection to the client, once everything queued is sent.
+    pub fn exit(self) {
+        drop(self.queue);
     }
 
     pub fn get_addr(&self) -> SocketAddr {
@@ -7,19 +7,28 @@ use tokio::sync::mpsc::Sender;
 pub struct ClientConnector {
     listen_stream: TcpListener,
     bus: Sender<Message>,
+    // Whether clients on this port get reads with participant info
+    enriched: bool,
 }
 
 impl ClientConnector {
-    pub async fn new(bind_port: u16, bus: Sender<Message>) -> Self {
+    pub async fn new(bind_port: u16, bus: Sender<Message>, enriched: bool) -> Self {
         // Bind to the listening port to allow other computers to connect
         let listener = TcpListener::bind(("0.0.0.0", bind_port))
             .await
             .expect("Unable to bind to port");
-        println!("Bound to port: {}", listener.local_addr().unwrap().port());
+        match enriched {
+            true => println!(
+                "Bound to participant port: {}",
+                listener.local_addr().unwrap().port()
+            ),
+            false => println!("Bound to port: {}", listener.local_addr().unwrap().port()),
+        };
 
         ClientConnector {
             listen_stream: listener,
             bus,
+            enriched,
         }
     }
 
@@ -34,10 +43,11 @@ impl ClientConnector {
                     match Client::new(stream, addr) {
                         Err(_) => eprintln!("\r\x1b[2KError connecting to client"),
                         Ok(client) => {
-                            self.bus
-                                .send(Message::CLIENT(client))
-                                .await
-                                .unwrap();
+                            let message = match self.enriched {
+                                true => Message::ENRICHED_CLIENT(client),
+                                false => Message::CLIENT(client),
+                            };
+                            self.bus.send(message).await.unwrap();
                             println!("\r\x1b[2KConnected to client: {}", addr)
                         }
                     };
@@ -1,78 +1,117 @@
 use super::Client;
 use crate::models::Message;
-use crate::models::{ChipRead, Gender, Participant};
-use futures::future::join_all;
+use crate::models::ChipRead;
 use rusqlite::Connection;
+use std::collections::VecDeque;
 use std::convert::TryFrom;
 use std::fs::File;
 use std::io::{self, Write};
+use std::net::SocketAddr;
 use std::path::Path;
 use tokio::sync::mpsc::Receiver;
 
-fn read_to_string(read: &str, conn: &rusqlite::Connection, read_count: &u32) -> String {
+// The most reads kept for replaying to new clients
+const REPLAY_BUFFER_SIZE: usize = 10000;
+
+/// Look up the bib and name of the participant wearing a chip.
+///
+/// Returns None if the chip isn't in the bib-chip file. The name is None if
+/// the bib isn't in the participant file.
+fn lookup_participant(tag_id: &str, conn: &Connection) -> Option<(i32, Option<String>)> {
+    let mut stmt = conn
+        .prepare(
+            "SELECT
+                    c.bib,
+                    p.first_name,
+                    p.last_name
+                    FROM chip c
+                    LEFT JOIN participant p
+                    ON c.bib = p.bib
+                    WHERE c.id = ?",
+        )
+        .unwrap();
+    // Make the query and map to a bib and name
+    stmt.query_row(&[tag_id], |row| {
+        let first_name: Option<String> = row.get(1)?;
+        let last_name: Option<String> = row.get(2)?;
+        let name = match (first_name, last_name) {
+            (Some(first), Some(last)) => Some(format!("{} {}", first, last)),
+            _ => None,
+        };
+        Ok((row.get(0)?, name))
+    })
+    .ok()
+}
+
+fn read_to_string(read: &str, conn: &Connection, read_count: &u32) -> String {
     match ChipRead::try_from(read) {
         Err(desc) => format!("Error reading chip {}", desc),
-        Ok(read) => {
-            let mut stmt = conn
-                .prepare(
-                    "SELECT
-                            c.id,
-                            c.bib,
-                            p.first_name,
-                            p.last_name
-                            FROM chip c
-                            LEFT JOIN participant p
-                            ON c.bib = p.bib
-                            WHERE c.id = ?",
-                )
-                .unwrap();
-            // Make the query and map to a participant
-            let row = stmt.query_row(&[read.tag_id.as_str()], |row| {
-                Ok(Participant {
-                    // If there is a missing field, then map it to unknown
-                    chip_id: vec![row.get(0).unwrap_or("None".to_owned())],
-                    bib: row.get(1).unwrap_or(0),
-                    first_name: row.get(2).unwrap_or("Unknown".to_owned()),
-                    last_name: row.get(3).unwrap_or("Participant".to_owned()),
-                    gender: Gender::X,
-                    age: None,
-                    affiliation: None,
-                    division: None,
-                })
-            });
+        Ok(read) => match lookup_participant(&read.tag_id, conn) {
+            // Bandit chip
+            None => format!(
+                "Total Reads: {} Last Read: Unknown Chip {} {}",
+                read_count,
+                read.tag_id,
+                read.time_string()
+            ),
+            // Good chip, either good or unknown participant
+            Some((bib, name)) => format!(
+                "Total Reads: {} Last Read: {} {} {}",
+                read_count,
+                bib,
+                name.unwrap_or_else(|| "Unknown Participant".to_owned()),
+                read.time_string()
+            ),
+        },
+    }
+}
 
-            match row {
-                // Bandit chip
-                Err(_) => format!(
-                    "Total Reads: {} Last Read: Unknown Chip {} {}",
-                    read_count,
-                    read.tag_id,
-                    read.time_string()
-                ),
-                // Good chip, either good or unknown participant
-                Ok(participant) => {
-                    // println!("{:?}", participant);
-                    format!(
-                        "Total Reads: {} Last Read: {} {} {} {}",
-                        read_count,
-                        participant.bib,
-                        participant.first_name,
-                        participant.last_name,
-                        read.time_string()
-                    )
-                }
-            }
-        }
+/// Convert a read into a line of the form `bib,name,HH:MM:SS.mmm`.
+///
+/// The bib and name are empty if they aren't known. Returns None if the read
+/// can't be parsed.
+fn read_to_enriched(read: &str, conn: &Connection) -> Option<String> {
+    let read = ChipRead::try_from(read).ok()?;
+    let (bib, name) = match lookup_participant(&read.tag_id, conn) {
+        Some((bib, name)) => (bib.to_string(), name.unwrap_or_default()),
+        None => (String::new(), String::new()),
+    };
+    Some(format!("{},{},{}\r\n", bib, name, read.time_string()))
+}
+
+/// Send a read to all the clients, split in two writes if asked.
+///
+/// If a client returns an error, it is removed from future transmissions.
+fn send_to_clients(clients: &mut Vec<Client>, read: &str, split: bool) {
+    let failed: Vec<SocketAddr> = clients
+        .iter_mut()
+        .filter_map(|c| {
+            let result = match split {
+                true => c.send_split_read(read.to_owned()),
+                false => c.send_read(read.to_owned()),
+            };
+            result.err()
+        })
+        .collect();
+    for addr in failed {
+        clients.retain(|c| c.get_addr() != addr);
+        println!("\r\x1b[2KDisconnected from client: {}", addr);
     }
 }
 
 /// Contains a vec of all the clients and forwards reads to them
 pub struct ClientPool {
     clients: Vec<Client>,
+    // Clients that get reads with participant info
+    enriched_clients: Vec<Client>,
     bus: Receiver<Message>,
     file_writer: Option<File>,
     buffered_output: bool,
     db_conn: Option<Connection>,
+    // Reads to send to new clients when they connect
+    replay_buffer: Option<VecDeque<String>>,
+    // Send reads in two writes
+    split: bool,
 }
 
 impl ClientPool {
@@ -81,6 +120,8 @@ impl ClientPool {
         db_conn: Option<Connection>,
         out_file: Option<String>,
         buffered_output: bool,
+        replay: bool,
+        split: bool,
     ) -> Self {
         // Check if the user has specified to save the reads to a file
         let mut file_writer: Option<File> = None;
@@ -101,10 +142,16 @@ impl ClientPool {
 
         ClientPool {
             clients: Vec::new(),
+            enriched_clients: Vec::new(),
             bus,
             file_writer,
             buffered_output,
             db_conn,
+            replay_buffer: match replay {
+                true => Some(VecDeque::new()),
+                false => None,
+            },
+            split,
         }
     }
 
@@ -146,35 +193,118 @@ impl ClientPool {
                         }
                         None => {}
                     }
-                    let mut futures = Vec::new();
-                    for client in self.clients.iter_mut() {
-                        futures.push(client.send_read(r.clone()));
+                    if let Some(buffer) = self.replay_buffer.as_mut() {
+                        buffer.push_back(r.clone());
+                        if buffer.len() > REPLAY_BUFFER_SIZE {
+                            buffer.pop_front();
+                        }
                     }
-                    let results = join_all(futures).await;
-                    // If a client returned an error, remove it from future
-                    // transmissions.
-                    for r in results.iter() {
-                        if r.is_err() {
-                            let pos = self
-                                .clients
-                                .iter()
-                                .position(|c| c.get_addr() == r.err().unwrap());
-                            if pos.is_some() {
-                                self.clients.remove(pos.unwrap());
-                            }
+                    send_to_clients(&mut self.clients, &r, self.split);
+                    // Enriching needs a participant lookup, so skip it if no
+                    // one is listening
+                    if !self.enriched_clients.is_empty() {
+                        let enriched = match &self.db_conn {
+                            Some(conn) => read_to_enriched(&r, conn),
+                            None => None,
+                        };
+                        if let Some(line) = enriched {
+                            send_to_clients(&mut self.enriched_clients, &line, self.split);
                         }
                     }
                 }
+                Message::STATUS(line) => {
+                    // Not a read, so it isn't counted, saved or replayed
+                    send_to_clients(&mut self.clients, &line, false);
+                }
                 Message::SHUTDOWN => {
                     for client in self.clients {
                         client.exit();
                     }
+                    for client in self.enriched_clients {
+                        client.exit();
+                    }
                     return;
                 }
-                Message::CLIENT(c) => {
+                Message::CLIENT(mut c) => {
+                    // Send the new client everything it missed, like a reader
+                    // that re-sends its buffer on reconnect
+                    if let Some(buffer) = &self.replay_buffer {
+                        if !buffer.is_empty()
+                            && c.send_read(buffer.iter().map(|r| r.as_str()).collect()).is_err()
+                        {
+                            println!("\r\x1b[2KDisconnected from client: {}", c.get_addr());
+                            continue;
+                        }
+                    }
                     self.clients.push(c);
                 }
+                Message::ENRICHED_CLIENT(c) => {
+                    self.enriched_clients.push(c);
+                }
             }
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rusqlite::NO_PARAMS;
+
+    fn test_db() -> Connection {
+        let conn = Connection::open_in_memory().unwrap();
+        conn.execute(
+            "CREATE TABLE participant (
+                      bib           INTEGER PRIMARY KEY,
+                      first_name    TEXT NOT NULL,
+                      last_name     TEXT NOT NULL
+                      )",
+            NO_PARAMS,
+        )
+        .unwrap();
+        conn.execute(
+            "CREATE TABLE chip (
+                      id     TEXT PRIMARY KEY,
+                      bib    INTEGER NOT NULL
+                      )",
+            NO_PARAMS,
+        )
+        .unwrap();
+        conn.execute(
+            "INSERT INTO chip (id, bib) VALUES ('000000012345', 12), ('000000054321', 34)",
+            NO_PARAMS,
+        )
+        .unwrap();
+        conn.execute(
+            "INSERT INTO participant (bib, first_name, last_name) VALUES (12, 'John', 'Smith')",
+            NO_PARAMS,
+        )
+        .unwrap();
+        conn
+    }
+
+    #[test]
+    fn enriched_known_participant() {
+        let line = read_to_enriched("aa400000000123450a2a01123018455927a7", &test_db());
+        assert_eq!(line, Some("12,John Smith,18:45:59.390\r\n".to_owned()));
+    }
+
+    #[test]
+    fn enriched_unknown_participant() {
+        // In the bib-chip file, but not the participant file
+        let line = read_to_enriched("aa400000000543210a2a01123018455927a7", &test_db());
+        assert_eq!(line, Some("34,,18:45:59.390\r\n".to_owned()));
+    }
+
+    #[test]
+    fn enriched_unknown_chip() {
+        let line = read_to_enriched("aa400000000999990a2a01123018455927c5", &test_db());
+        assert_eq!(line, Some(",,18:45:59.390\r\n".to_owned()));
+    }
+
+    #[test]
+    fn enriched_invalid_read() {
+        let line = read_to_enriched("aa400000000123450a2a01123018455927a8", &test_db());
+        assert_eq!(line, None);
+    }
+}
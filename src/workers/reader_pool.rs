@@ -2,6 +2,7 @@ use super::TimingReader;
 use crate::models::{ReadType, Message};
 use futures::future::join_all;
 use std::net::SocketAddrV4;
+use std::time::Duration;
 use tokio::sync::mpsc::Sender;
 
 /// Contains a vec of the readers and runs them asynchronously
@@ -13,10 +14,16 @@ pub struct ReaderPool {
 }
 
 impl ReaderPool {
-    pub fn new(reader_addrs: Vec<SocketAddrV4>, bus: Sender<Message>, read_type: ReadType) -> Self {
+    pub fn new(
+        reader_addrs: Vec<SocketAddrV4>,
+        bus: Sender<Message>,
+        read_type: ReadType,
+        idle_timeout: Option<Duration>,
+        max_drift: Option<Duration>,
+    ) -> Self {
         let readers = reader_addrs
             .iter()
-            .map(|a| TimingReader::new(*a, read_type, bus.clone()))
+            .map(|a| TimingReader::new(*a, read_type, bus.clone(), idle_timeout, max_drift))
             .collect();
         ReaderPool { readers, bus, read_type }
     }
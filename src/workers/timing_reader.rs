@@ -1,8 +1,65 @@
-use crate::models::{ReadType, Message};
+use crate::models::{ChipRead, ReadType, Message};
+use chrono::Local;
 use std::net::SocketAddrV4;
+use std::time::{Duration, Instant};
 use tokio::net::TcpStream;
 use tokio::prelude::*;
 use tokio::sync::mpsc::Sender;
+use tokio::time::timeout;
+
+// How much each new read moves the clock drift estimate
+const DRIFT_WEIGHT: f64 = 0.1;
+// Reads used to make the first clock drift estimate
+const DRIFT_WARMUP: usize = 5;
+// The most a read can differ from the estimate, in milliseconds. Limits how
+// far one late read can move the estimate.
+const DRIFT_MAX_STEP: f64 = 5000.0;
+// Readers re-send their buffer on reconnect, so ignore the clock of reads
+// that arrive this soon after connecting
+const REPLAY_GRACE: Duration = Duration::from_secs(5);
+
+/// A rolling estimate of how far a reader's clock is from ours
+#[derive(Debug, Default)]
+struct ClockDrift {
+    // The estimate in milliseconds, once there have been enough reads
+    estimate: Option<f64>,
+    // Reads collected for the first estimate
+    warmup: Vec<f64>,
+    over: bool,
+}
+
+impl ClockDrift {
+    /// Add the difference between the reader's clock and ours for a read, in
+    /// milliseconds.
+    ///
+    /// Returns Some(true) when the estimate goes over the limit, and
+    /// Some(false) when it comes back within it.
+    fn add_sample(&mut self, sample: f64, max_drift: f64) -> Option<bool> {
+        let estimate = match self.estimate {
+            Some(estimate) => {
+                estimate + DRIFT_WEIGHT * (sample - estimate).clamp(-DRIFT_MAX_STEP, DRIFT_MAX_STEP)
+            }
+            None => {
+                self.warmup.push(sample);
+                if self.warmup.len() < DRIFT_WARMUP {
+                    return None;
+                }
+                // Use the median so a few late reads don't skew it
+                self.warmup.sort_by(f64::total_cmp);
+                let median = self.warmup[self.warmup.len() / 2];
+                self.warmup.clear();
+                median
+            }
+        };
+        self.estimate = Some(estimate);
+        let over = estimate.abs() > max_drift;
+        if over == self.over {
+            return None;
+        }
+        self.over = over;
+        Some(over)
+    }
+}
 
 /// Receives reads from the reader, then forwards them to the client pool.
 #[derive(Debug)]
@@ -11,10 +68,22 @@ pub struct TimingReader {
     read_type: ReadType,
     stream: Option<TcpStream>,
     chip_read_bus: Sender<Message>,
+    // Reconnect if the reader is silent for this long
+    idle_timeout: Option<Duration>,
+    // Warn if the reader's clock is further than this from ours
+    max_drift: Option<Duration>,
+    drift: ClockDrift,
+    connected_at: Option<Instant>,
 }
 
 impl TimingReader {
-    pub fn new(addr: SocketAddrV4, read_type: ReadType, chip_read_bus: Sender<Message>) -> Self {
+    pub fn new(
+        addr: SocketAddrV4,
+        read_type: ReadType,
+        chip_read_bus: Sender<Message>,
+        idle_timeout: Option<Duration>,
+        max_drift: Option<Duration>,
+    ) -> Self {
         println!("Waiting for reader: {}", addr);
 
         TimingReader {
@@ -22,9 +91,47 @@ impl TimingReader {
             read_type,
             stream: None::<TcpStream>,
             chip_read_bus,
+            idle_timeout,
+            max_drift,
+            drift: ClockDrift::default(),
+            connected_at: None,
         }
     }
 
+    /// Update the estimate of how far the reader's clock is from ours, and
+    /// warn when it goes over the limit.
+    fn check_drift(&mut self, read: &str) {
+        let max_drift = match self.max_drift {
+            Some(max_drift) => max_drift,
+            None => return,
+        };
+        match self.connected_at {
+            Some(connected_at) if connected_at.elapsed() >= REPLAY_GRACE => {}
+            _ => return,
+        };
+        let reader_time = match ChipRead::parse(read)
+            .ok()
+            .and_then(|r| r.timestamp.to_datetime())
+        {
+            Some(time) => time,
+            None => return,
+        };
+        let sample = (reader_time - Local::now().naive_local()).num_milliseconds() as f64;
+        match self.drift.add_sample(sample, max_drift.as_millis() as f64) {
+            Some(true) => println!(
+                "\r\x1b[2KReader {} clock is off by {:.1}s",
+                self.addr,
+                self.drift.estimate.unwrap_or(0.0) / 1000.0
+            ),
+            Some(false) => println!(
+                "\r\x1b[2KReader {} clock is back within {}s",
+                self.addr,
+                max_drift.as_secs()
+            ),
+            None => {}
+        };
+    }
+
     /// Start listening for reads.
     ///
     /// This function should never return.
@@ -34,7 +141,27 @@ impl TimingReader {
             match self.stream.as_mut() {
                 Some(stream) => {
                     // Get 38 bytes from the stream, which is exactly 1 read
-                    match stream.read_exact(&mut input_buffer).await {
+                    let read_fut = stream.read_exact(&mut input_buffer);
+                    let result = match self.idle_timeout {
+                        Some(idle) => match timeout(idle, read_fut).await {
+                            Ok(result) => result,
+                            Err(_) => {
+                                // Some readers hang while keeping the session
+                                // open, so drop it and reconnect. This times
+                                // whole reads, not bytes, so a partial read
+                                // followed by silence also counts as stale.
+                                println!(
+                                    "\r\x1b[2KReader {} is stale, no complete read for {}s. Reconnecting.",
+                                    self.addr,
+                                    idle.as_secs()
+                                );
+                                self.stream = None::<TcpStream>;
+                                continue;
+                            }
+                        },
+                        None => read_fut.await,
+                    };
+                    match result {
                         Ok(_) => {}
                         Err(e) => {
                             println!("\r\x1b[2KError reading from reader: {}", e);
@@ -50,6 +177,7 @@ impl TimingReader {
                             continue;
                         }
                     };
+                    self.check_drift(read);
                     // Send the read to the threads
                     self.chip_read_bus
                         .send(Message::CHIP_READ(read.to_owned()))
@@ -72,8 +200,68 @@ impl TimingReader {
                         }
                     };
                     self.stream = Some(stream);
+                    // Start a new estimate, as the reader may have been reset
+                    self.drift = ClockDrift::default();
+                    self.connected_at = Some(Instant::now());
                 }
             }
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn drift_warmup() {
+        let mut drift = ClockDrift::default();
+        for _ in 0..DRIFT_WARMUP - 1 {
+            assert_eq!(drift.add_sample(100.0, 2000.0), None);
+        }
+        assert_eq!(drift.estimate, None);
+        assert_eq!(drift.add_sample(100.0, 2000.0), None);
+        assert_eq!(drift.estimate, Some(100.0));
+    }
+
+    #[test]
+    fn drift_warmup_ignores_buffered_read() {
+        // A read from hours ago shouldn't set the first estimate
+        let mut drift = ClockDrift::default();
+        assert_eq!(drift.add_sample(-3_600_000.0, 2000.0), None);
+        for _ in 0..DRIFT_WARMUP - 1 {
+            assert_eq!(drift.add_sample(100.0, 2000.0), None);
+        }
+        assert_eq!(drift.estimate, Some(100.0));
+    }
+
+    #[test]
+    fn drift_late_read_clamped() {
+        let mut drift = ClockDrift::default();
+        for _ in 0..DRIFT_WARMUP {
+            drift.add_sample(0.0, 2000.0);
+        }
+        assert_eq!(drift.add_sample(-3_600_000.0, 2000.0), None);
+        assert_eq!(drift.estimate, Some(-DRIFT_WEIGHT * DRIFT_MAX_STEP));
+    }
+
+    #[test]
+    fn drift_over_and_back() {
+        let mut drift = ClockDrift::default();
+        for _ in 0..DRIFT_WARMUP - 1 {
+            drift.add_sample(3000.0, 2000.0);
+        }
+        assert_eq!(drift.add_sample(3000.0, 2000.0), Some(true));
+        assert_eq!(drift.add_sample(3000.0, 2000.0), None);
+
+        // Only warns again once the estimate comes back
+        let mut change = None;
+        for _ in 0..100 {
+            if let Some(over) = drift.add_sample(0.0, 2000.0) {
+                change = Some(over);
+            }
+        }
+        assert_eq!(change, Some(false));
+        assert!(drift.estimate.unwrap() < 2000.0);
+    }
+}